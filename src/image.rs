@@ -0,0 +1,218 @@
+//! `image` crate integration.
+//!
+//! Converts decode output into a typed [`image::DynamicImage`] and encodes
+//! from one, using the [`FrameInfo`] to pick the pixel type. The stride
+//! math and byte-order handling that otherwise falls on every caller of
+//! [`CharLS::decode`] lives here instead.
+
+use ::image::{DynamicImage, ImageBuffer, Luma, Rgb};
+use charls_sys::*;
+
+use crate::{CharLS, CharlsResult, Error, FrameInfo, InterleaveMode};
+
+impl CharLS {
+    /// Decode `src` into a [`DynamicImage`], choosing the pixel type from
+    /// the frame info: a single component maps to `Luma8`/`Luma16`, three
+    /// components to `Rgb8`/`Rgb16`, depending on the bit depth.
+    pub fn decode_image(&mut self, src: &[u8]) -> CharlsResult<DynamicImage> {
+        let (frame_info, data) = self.decode_frame(src)?;
+        to_dynamic_image(&frame_info, &data)
+    }
+
+    /// Encode a [`DynamicImage`] at the given near-lossless value. The
+    /// component count, bit depth and interleaving are derived from the
+    /// image's pixel type.
+    pub fn encode_image(&mut self, image: &DynamicImage, near: i32) -> CharlsResult<Vec<u8>> {
+        let (frame_info, buffer) = from_dynamic_image(image)?;
+        self.encode(frame_info, near, &buffer)
+    }
+
+    /// Decode in a single pass, returning both the frame info and the raw
+    /// samples so the pixel type can be chosen without re-reading the
+    /// header.
+    fn decode_frame(&mut self, src: &[u8]) -> CharlsResult<(FrameInfo, Vec<u8>)> {
+        let decoder = self.decoder.unwrap_or_else(|| {
+            self.decoder = Some(unsafe { charls_jpegls_decoder_create() });
+            self.decoder.unwrap()
+        });
+
+        if decoder.is_null() {
+            return Err(Error::InitCodec);
+        }
+
+        let err = unsafe {
+            charls_jpegls_decoder_set_source_buffer(decoder, src.as_ptr() as _, src.len())
+        };
+        crate::translate_error(err)?;
+
+        let err = unsafe { charls_jpegls_decoder_read_header(decoder) };
+        crate::translate_error(err)?;
+
+        let mut frame_info = charls_frame_info {
+            width: 0,
+            height: 0,
+            bits_per_sample: 0,
+            component_count: 0,
+        };
+        let err = unsafe { charls_jpegls_decoder_get_frame_info(decoder, &mut frame_info) };
+        crate::translate_error(err)?;
+
+        let mut interleave_mode: charls_interleave_mode = 0;
+        let err = unsafe {
+            charls_jpegls_decoder_get_interleave_mode(decoder, &mut interleave_mode)
+        };
+        crate::translate_error(err)?;
+
+        let mut size: usize = 0;
+        let err =
+            unsafe { charls_jpegls_decoder_get_destination_size(decoder, 0, &mut size) };
+        crate::translate_error(err)?;
+
+        let mut dst = vec![0; size];
+        let err = unsafe {
+            charls_jpegls_decoder_decode_to_buffer(decoder, dst.as_mut_ptr() as _, size, 0)
+        };
+        crate::translate_error(err)?;
+
+        let frame_info = FrameInfo {
+            width: frame_info.width,
+            height: frame_info.height,
+            bits_per_sample: frame_info.bits_per_sample,
+            component_count: frame_info.component_count,
+            interleave_mode: InterleaveMode::from_native(interleave_mode),
+        };
+
+        Ok((frame_info, dst))
+    }
+}
+
+fn to_dynamic_image(frame_info: &FrameInfo, data: &[u8]) -> CharlsResult<DynamicImage> {
+    let width = frame_info.width;
+    let height = frame_info.height;
+
+    match (frame_info.component_count, frame_info.bits_per_sample) {
+        (1, bits) if bits <= 8 => ImageBuffer::<Luma<u8>, _>::from_raw(width, height, data.to_vec())
+            .map(DynamicImage::ImageLuma8)
+            .ok_or(Error::UnsupportedImage),
+        (1, bits) if bits <= 16 => {
+            let samples = to_u16_samples(data);
+            ImageBuffer::<Luma<u16>, _>::from_raw(width, height, samples)
+                .map(DynamicImage::ImageLuma16)
+                .ok_or(Error::UnsupportedImage)
+        }
+        (3, bits) if bits <= 8 => {
+            let interleaved = interleave(
+                data,
+                width as usize,
+                height as usize,
+                3,
+                frame_info.interleave_mode,
+            );
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, interleaved)
+                .map(DynamicImage::ImageRgb8)
+                .ok_or(Error::UnsupportedImage)
+        }
+        (3, bits) if bits <= 16 => {
+            let samples = to_u16_samples(data);
+            let interleaved = interleave(
+                &samples,
+                width as usize,
+                height as usize,
+                3,
+                frame_info.interleave_mode,
+            );
+            ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, interleaved)
+                .map(DynamicImage::ImageRgb16)
+                .ok_or(Error::UnsupportedImage)
+        }
+        _ => Err(Error::UnsupportedImage),
+    }
+}
+
+fn from_dynamic_image(image: &DynamicImage) -> CharlsResult<(FrameInfo, Vec<u8>)> {
+    match image {
+        DynamicImage::ImageLuma8(buffer) => Ok((
+            frame_info(buffer.width(), buffer.height(), 8, 1, InterleaveMode::None),
+            buffer.as_raw().clone(),
+        )),
+        DynamicImage::ImageLuma16(buffer) => Ok((
+            frame_info(buffer.width(), buffer.height(), 16, 1, InterleaveMode::None),
+            from_u16_samples(buffer.as_raw()),
+        )),
+        DynamicImage::ImageRgb8(buffer) => Ok((
+            frame_info(buffer.width(), buffer.height(), 8, 3, InterleaveMode::Sample),
+            buffer.as_raw().clone(),
+        )),
+        DynamicImage::ImageRgb16(buffer) => Ok((
+            frame_info(buffer.width(), buffer.height(), 16, 3, InterleaveMode::Sample),
+            from_u16_samples(buffer.as_raw()),
+        )),
+        _ => Err(Error::UnsupportedImage),
+    }
+}
+
+fn frame_info(
+    width: u32,
+    height: u32,
+    bits_per_sample: i32,
+    component_count: i32,
+    interleave_mode: InterleaveMode,
+) -> FrameInfo {
+    FrameInfo {
+        width,
+        height,
+        bits_per_sample,
+        component_count,
+        interleave_mode,
+    }
+}
+
+/// Repack the decoded samples into the per-sample interleaved layout the
+/// `image` crate expects, honoring the stream's interleave mode.
+fn interleave<T: Copy + Default>(
+    samples: &[T],
+    width: usize,
+    height: usize,
+    components: usize,
+    mode: InterleaveMode,
+) -> Vec<T> {
+    match mode {
+        InterleaveMode::Sample => samples.to_vec(),
+        InterleaveMode::None => {
+            let plane = width * height;
+            let mut out = vec![T::default(); samples.len()];
+            for c in 0..components {
+                for i in 0..plane {
+                    out[i * components + c] = samples[c * plane + i];
+                }
+            }
+            out
+        }
+        InterleaveMode::Line => {
+            let mut out = vec![T::default(); samples.len()];
+            for y in 0..height {
+                let row = y * width * components;
+                for c in 0..components {
+                    for x in 0..width {
+                        out[row + x * components + c] = samples[row + c * width + x];
+                    }
+                }
+            }
+            out
+        }
+    }
+}
+
+fn to_u16_samples(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+        .collect()
+}
+
+fn from_u16_samples(samples: &[u16]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(samples.len() * 2);
+    for sample in samples {
+        out.extend_from_slice(&sample.to_ne_bytes());
+    }
+    out
+}
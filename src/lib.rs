@@ -8,6 +8,8 @@
 //!   If this is not enabled,
 //!   you need to install the CharLS (e.g. `libcharls.so`) into your system
 //!   or add it to your library path (`LD_LIBRARY_PATH`).
+//! - `image`: convert decode output into an [`image::DynamicImage`] and
+//!   encode from one, choosing the pixel type from the frame info.
 //!
 //! # Example
 //!
@@ -24,6 +26,9 @@
 use charls_sys::*;
 use std::ffi::CStr;
 
+#[cfg(feature = "image")]
+mod image;
+
 pub type CharlsResult<T> = Result<T, Error>;
 pub type CharlsEncoder = *mut charls_jpegls_encoder;
 pub type CharlsDecoder = *mut charls_jpegls_decoder;
@@ -36,19 +41,60 @@ pub enum Error {
     InitCodec,
     /// Unable to compute decompressed size
     ComputeSize,
-    /// CharLS error
-    JpegLsError {
+    /// An argument passed to CharLS was invalid
+    InvalidArgument,
+    /// A parameter value is not supported by CharLS
+    ParameterValueNotSupported,
+    /// The destination buffer is too small to hold the output
+    DestinationTooSmall,
+    /// The source buffer is too small to hold the encoded data
+    SourceTooSmall,
+    /// The encoded data is invalid
+    InvalidEncodedData,
+    /// The frame's bit depth is not supported
+    UnsupportedBitDepth,
+    /// The frame's component count is not supported
+    UnsupportedComponentCount,
+    /// The image geometry cannot be represented as an `image` crate pixel type
+    #[cfg(feature = "image")]
+    UnsupportedImage,
+    /// Any other native CharLS error, preserved for forward compatibility
+    Unknown {
         /// the native error code from CharLS
         code: charls_jpegls_errc
     },
 }
 
+impl Error {
+    fn from_code(code: charls_jpegls_errc) -> Self {
+        match code {
+            CHARLS_JPEGLS_ERRC_INVALID_ARGUMENT => Error::InvalidArgument,
+            CHARLS_JPEGLS_ERRC_PARAMETER_VALUE_NOT_SUPPORTED => Error::ParameterValueNotSupported,
+            CHARLS_JPEGLS_ERRC_DESTINATION_BUFFER_TOO_SMALL => Error::DestinationTooSmall,
+            CHARLS_JPEGLS_ERRC_SOURCE_BUFFER_TOO_SMALL => Error::SourceTooSmall,
+            CHARLS_JPEGLS_ERRC_INVALID_ENCODED_DATA => Error::InvalidEncodedData,
+            CHARLS_JPEGLS_ERRC_INVALID_ARGUMENT_BITS_PER_SAMPLE => Error::UnsupportedBitDepth,
+            CHARLS_JPEGLS_ERRC_INVALID_ARGUMENT_COMPONENT_COUNT => Error::UnsupportedComponentCount,
+            _ => Error::Unknown { code },
+        }
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Error::InitCodec => write!(f, "Unable to start the codec"),
             Error::ComputeSize => write!(f, "Unable to compute decompressed size"),
-            Error::JpegLsError { code } => {
+            Error::InvalidArgument => write!(f, "Invalid argument"),
+            Error::ParameterValueNotSupported => write!(f, "Parameter value not supported"),
+            Error::DestinationTooSmall => write!(f, "Destination buffer too small"),
+            Error::SourceTooSmall => write!(f, "Source buffer too small"),
+            Error::InvalidEncodedData => write!(f, "Invalid encoded data"),
+            Error::UnsupportedBitDepth => write!(f, "Unsupported bit depth"),
+            Error::UnsupportedComponentCount => write!(f, "Unsupported component count"),
+            #[cfg(feature = "image")]
+            Error::UnsupportedImage => write!(f, "Unsupported image pixel layout"),
+            Error::Unknown { code } => {
                 let message = unsafe {
                     let msg = charls_get_error_message(*code);
                     CStr::from_ptr(msg)
@@ -61,11 +107,212 @@ impl std::fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+/// Component interleaving used when encoding or decoding multi-component
+/// (e.g. color) images.
+///
+/// This mirrors CharLS's `charls_interleave_mode`. For single-component
+/// images the mode is irrelevant and [`InterleaveMode::None`] applies;
+/// for RGB and other multi-component images it selects how samples are
+/// laid out in the uncompressed buffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InterleaveMode {
+    /// Components are stored in separate planes (planar layout).
+    None,
+    /// Components are interleaved per line.
+    Line,
+    /// Components are interleaved per sample (e.g. `RGBRGB...`).
+    Sample,
+}
+
+impl Default for InterleaveMode {
+    fn default() -> Self {
+        InterleaveMode::None
+    }
+}
+
+impl InterleaveMode {
+    fn to_native(self) -> charls_interleave_mode {
+        match self {
+            InterleaveMode::None => 0,
+            InterleaveMode::Line => 1,
+            InterleaveMode::Sample => 2,
+        }
+    }
+
+    fn from_native(value: charls_interleave_mode) -> Self {
+        match value {
+            1 => InterleaveMode::Line,
+            2 => InterleaveMode::Sample,
+            _ => InterleaveMode::None,
+        }
+    }
+}
+
+/// Color space of the samples as recorded in a SPIFF header.
+///
+/// Mirrors CharLS's `charls_spiff_color_space`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpiffColorSpace {
+    /// No color space is specified.
+    None,
+    /// Bi-level image, 0 is white, 1 is black.
+    BiLevelBlack,
+    /// YCbCr, ITU-T BT.709 video.
+    YcbcrItuBt709Video,
+    /// No color space but the component count is known.
+    NoneNonStandard,
+    /// YCbCr, ITU-T BT.601-1 RGB.
+    YcbcrItuBt601_1Rgb,
+    /// YCbCr, ITU-T BT.601-1 video.
+    YcbcrItuBt601_1Video,
+    /// Grayscale, ISO 8613.
+    Grayscale,
+    /// Photo YCC.
+    PhotoYcc,
+    /// RGB.
+    Rgb,
+    /// CMY.
+    Cmy,
+    /// CMYK.
+    Cmyk,
+    /// YCCK.
+    Ycck,
+    /// CIE Lab.
+    CieLab,
+    /// Bi-level image, 0 is black, 1 is white.
+    BiLevelWhite,
+}
+
+impl Default for SpiffColorSpace {
+    fn default() -> Self {
+        SpiffColorSpace::None
+    }
+}
+
+impl SpiffColorSpace {
+    fn to_native(self) -> charls_spiff_color_space {
+        match self {
+            SpiffColorSpace::None => 0,
+            SpiffColorSpace::BiLevelBlack => 1,
+            SpiffColorSpace::YcbcrItuBt709Video => 2,
+            SpiffColorSpace::NoneNonStandard => 3,
+            SpiffColorSpace::YcbcrItuBt601_1Rgb => 4,
+            SpiffColorSpace::YcbcrItuBt601_1Video => 5,
+            SpiffColorSpace::Grayscale => 8,
+            SpiffColorSpace::PhotoYcc => 9,
+            SpiffColorSpace::Rgb => 10,
+            SpiffColorSpace::Cmy => 11,
+            SpiffColorSpace::Cmyk => 12,
+            SpiffColorSpace::Ycck => 13,
+            SpiffColorSpace::CieLab => 14,
+            SpiffColorSpace::BiLevelWhite => 15,
+        }
+    }
+
+    fn from_native(value: charls_spiff_color_space) -> Self {
+        match value {
+            1 => SpiffColorSpace::BiLevelBlack,
+            2 => SpiffColorSpace::YcbcrItuBt709Video,
+            3 => SpiffColorSpace::NoneNonStandard,
+            4 => SpiffColorSpace::YcbcrItuBt601_1Rgb,
+            5 => SpiffColorSpace::YcbcrItuBt601_1Video,
+            8 => SpiffColorSpace::Grayscale,
+            9 => SpiffColorSpace::PhotoYcc,
+            10 => SpiffColorSpace::Rgb,
+            11 => SpiffColorSpace::Cmy,
+            12 => SpiffColorSpace::Cmyk,
+            13 => SpiffColorSpace::Ycck,
+            14 => SpiffColorSpace::CieLab,
+            15 => SpiffColorSpace::BiLevelWhite,
+            _ => SpiffColorSpace::None,
+        }
+    }
+}
+
+/// Units of the resolution fields in a SPIFF header.
+///
+/// Mirrors CharLS's `charls_spiff_resolution_units`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpiffResolutionUnits {
+    /// The resolution fields express an aspect ratio.
+    AspectRatio,
+    /// Dots per inch.
+    DotsPerInch,
+    /// Dots per centimeter.
+    DotsPerCentimeter,
+}
+
+impl Default for SpiffResolutionUnits {
+    fn default() -> Self {
+        SpiffResolutionUnits::AspectRatio
+    }
+}
+
+impl SpiffResolutionUnits {
+    fn to_native(self) -> charls_spiff_resolution_units {
+        match self {
+            SpiffResolutionUnits::AspectRatio => 0,
+            SpiffResolutionUnits::DotsPerInch => 1,
+            SpiffResolutionUnits::DotsPerCentimeter => 2,
+        }
+    }
+
+    fn from_native(value: charls_spiff_resolution_units) -> Self {
+        match value {
+            1 => SpiffResolutionUnits::DotsPerInch,
+            2 => SpiffResolutionUnits::DotsPerCentimeter,
+            _ => SpiffResolutionUnits::AspectRatio,
+        }
+    }
+}
+
+/// SPIFF application header metadata carried alongside a JPEG-LS stream.
+///
+/// SPIFF headers record the color space, sample layout and resolution that
+/// higher-level pipelines (DICOM, archival) rely on. When writing, the
+/// component count, dimensions and bit depth are derived from the frame
+/// info; only [`color_space`](Self::color_space) and the resolution fields
+/// are honored. When read back, the component count and bit depth are
+/// populated too; the image dimensions are not part of this struct.
+#[derive(Default, Debug)]
+pub struct SpiffHeader {
+    pub color_space: SpiffColorSpace,
+    pub component_count: i32,
+    pub bits_per_sample: i32,
+    pub resolution_units: SpiffResolutionUnits,
+    pub vertical_resolution: u32,
+    pub horizontal_resolution: u32,
+}
+
+/// JPEG-LS preset coding parameters (the LSE-marker values).
+///
+/// These tune the context model used by the codec. A field left at `0`
+/// means "use the CharLS default": the default `maximum_sample_value` is
+/// `2^bits_per_sample - 1`, the thresholds `threshold1`/`threshold2`/
+/// `threshold3` are derived from the maximum sample value and the
+/// near-lossless value by the standard's formula, and `reset_value`
+/// defaults to 64.
+#[derive(Default, Debug)]
+pub struct PresetCodingParameters {
+    /// Largest value a sample can have (MAXVAL).
+    pub maximum_sample_value: i32,
+    /// First gradient quantization threshold (T1).
+    pub threshold1: i32,
+    /// Second gradient quantization threshold (T2).
+    pub threshold2: i32,
+    /// Third gradient quantization threshold (T3).
+    pub threshold3: i32,
+    /// Counter threshold at which the adaptive statistics are halved (RESET).
+    pub reset_value: i32,
+}
+
 /// CharLS codec instance
 #[derive(Default)]
 pub struct CharLS {
     encoder: Option<CharlsEncoder>,
     decoder: Option<CharlsDecoder>,
+    spiff_header: Option<SpiffHeader>,
+    preset_coding_parameters: Option<PresetCodingParameters>,
 }
 
 #[derive(Default, Debug)]
@@ -74,6 +321,11 @@ pub struct FrameInfo {
     pub height: u32,
     pub bits_per_sample: i32,
     pub component_count: i32,
+    /// Component interleaving of the (de)compressed samples.
+    ///
+    /// On encode this selects the layout of the source buffer; after
+    /// [`CharLS::get_frame_info`] it reports the mode read from the stream.
+    pub interleave_mode: InterleaveMode,
 }
 
 impl CharLS {
@@ -132,6 +384,54 @@ impl CharLS {
         self.decode_with_stride(src, 0)
     }
 
+    /// Decode `src` directly into the caller-supplied `dst`, returning the
+    /// number of bytes written.
+    ///
+    /// The destination is validated against the size CharLS computes for
+    /// the given `stride`; [`Error::DestinationTooSmall`] is returned if it
+    /// cannot hold the decoded image. Reusing one buffer across many frames
+    /// avoids the per-frame allocation that [`decode`](Self::decode)
+    /// performs.
+    pub fn decode_into(&mut self, src: &[u8], dst: &mut [u8], stride: u32) -> CharlsResult<usize> {
+        let decoder = self.decoder.unwrap_or_else(|| {
+            self.decoder = Some(unsafe { charls_jpegls_decoder_create() });
+            self.decoder.unwrap()
+        });
+
+        if decoder.is_null() {
+            return Err(Error::InitCodec);
+        }
+
+        let err = unsafe {
+            charls_jpegls_decoder_set_source_buffer(decoder, src.as_ptr() as _, src.len())
+        };
+        translate_error(err)?;
+
+        let err = unsafe { charls_jpegls_decoder_read_header(decoder) };
+        translate_error(err)?;
+
+        let mut size: usize = 0;
+        let err =
+            unsafe { charls_jpegls_decoder_get_destination_size(decoder, stride, &mut size) };
+        translate_error(err)?;
+
+        if dst.len() < size {
+            return Err(Error::DestinationTooSmall);
+        }
+
+        let err = unsafe {
+            charls_jpegls_decoder_decode_to_buffer(
+                decoder,
+                dst.as_mut_ptr() as _,
+                dst.len(),
+                stride,
+            )
+        };
+        translate_error(err)?;
+
+        Ok(size)
+    }
+
     pub fn encode(
         &mut self,
         frame_info: FrameInfo,
@@ -147,6 +447,7 @@ impl CharLS {
             return Err(Error::InitCodec);
         }
 
+        let interleave_mode = frame_info.interleave_mode;
         let frame_info = charls_frame_info {
             width: frame_info.width,
             height: frame_info.height,
@@ -177,16 +478,53 @@ impl CharLS {
 
         translate_error(err)?;
 
+        if let Some(header) = &self.spiff_header {
+            let err = unsafe {
+                charls_jpegls_encoder_write_standard_spiff_header(
+                    encoder,
+                    header.color_space.to_native(),
+                    header.resolution_units.to_native(),
+                    header.vertical_resolution,
+                    header.horizontal_resolution,
+                )
+            };
+
+            translate_error(err)?;
+        }
+
+        if let Some(params) = &self.preset_coding_parameters {
+            let params = charls_jpegls_pc_parameters {
+                maximum_sample_value: params.maximum_sample_value,
+                threshold1: params.threshold1,
+                threshold2: params.threshold2,
+                threshold3: params.threshold3,
+                reset_value: params.reset_value,
+            };
+            let err = unsafe {
+                charls_jpegls_encoder_set_preset_coding_parameters(
+                    encoder,
+                    &params as *const charls_jpegls_pc_parameters,
+                )
+            };
+
+            translate_error(err)?;
+        }
+
         let err = unsafe { charls_jpegls_encoder_set_near_lossless(encoder, near) };
 
         translate_error(err)?;
 
-        let mut data = src.to_vec();
+        let err = unsafe {
+            charls_jpegls_encoder_set_interleave_mode(encoder, interleave_mode.to_native())
+        };
+
+        translate_error(err)?;
+
         let err = unsafe {
             charls_jpegls_encoder_encode_from_buffer(
                 encoder,
-                data.as_mut_ptr() as *mut std::os::raw::c_void,
-                data.len(),
+                src.as_ptr() as *mut std::os::raw::c_void,
+                src.len(),
                 0,
             )
         };
@@ -201,6 +539,74 @@ impl CharLS {
         Ok(dst)
     }
 
+    /// Register custom preset coding parameters to be applied to every
+    /// following [`encode`](Self::encode) on this instance; they stay set
+    /// until replaced by another call. Fields left at `0` fall back to the CharLS
+    /// defaults derived from the frame info and near-lossless value.
+    pub fn set_preset_coding_parameters(&mut self, parameters: PresetCodingParameters) {
+        self.preset_coding_parameters = Some(parameters);
+    }
+
+    /// Register a standard SPIFF header to be written before every following
+    /// [`encode`](Self::encode) on this instance; it stays set until replaced
+    /// by another call. The component count, dimensions and bit
+    /// depth are taken from the encoded frame info; the color space and
+    /// resolution fields come from `header`.
+    pub fn set_spiff_header(&mut self, header: SpiffHeader) {
+        self.spiff_header = Some(header);
+    }
+
+    /// Read the SPIFF header from `src`, if one is present.
+    ///
+    /// Returns `Ok(None)` when the stream does not start with a SPIFF
+    /// header.
+    pub fn read_spiff_header(&mut self, src: &[u8]) -> CharlsResult<Option<SpiffHeader>> {
+        let decoder = self.decoder.unwrap_or_else(|| {
+            self.decoder = Some(unsafe { charls_jpegls_decoder_create() });
+            self.decoder.unwrap()
+        });
+
+        if decoder.is_null() {
+            return Err(Error::InitCodec);
+        }
+
+        let err = unsafe {
+            charls_jpegls_decoder_set_source_buffer(decoder, src.as_ptr() as _, src.len())
+        };
+        translate_error(err)?;
+
+        let mut header = charls_spiff_header {
+            profile_id: 0,
+            component_count: 0,
+            height: 0,
+            width: 0,
+            color_space: 0,
+            bits_per_sample: 0,
+            compression_type: 0,
+            resolution_units: 0,
+            vertical_resolution: 0,
+            horizontal_resolution: 0,
+        };
+        let mut header_found: i32 = 0;
+        let err = unsafe {
+            charls_jpegls_decoder_read_spiff_header(decoder, &mut header, &mut header_found)
+        };
+        translate_error(err)?;
+
+        if header_found == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(SpiffHeader {
+            color_space: SpiffColorSpace::from_native(header.color_space),
+            component_count: header.component_count,
+            bits_per_sample: header.bits_per_sample,
+            resolution_units: SpiffResolutionUnits::from_native(header.resolution_units),
+            vertical_resolution: header.vertical_resolution,
+            horizontal_resolution: header.horizontal_resolution,
+        }))
+    }
+
     pub fn get_frame_info(&mut self, src: &[u8]) -> CharlsResult<FrameInfo>{
         let decoder = self.decoder.unwrap_or_else(|| {
             self.decoder = Some(unsafe { charls_jpegls_decoder_create() });
@@ -230,11 +636,18 @@ impl CharLS {
         };
         translate_error(err)?;
 
+        let mut interleave_mode: charls_interleave_mode = 0;
+        let err = unsafe {
+            charls_jpegls_decoder_get_interleave_mode(decoder, &mut interleave_mode)
+        };
+        translate_error(err)?;
+
         Ok(FrameInfo {
             width: frame_info.width,
             height: frame_info.height,
             bits_per_sample: frame_info.bits_per_sample,
-            component_count: frame_info.component_count
+            component_count: frame_info.component_count,
+            interleave_mode: InterleaveMode::from_native(interleave_mode),
         })
     }
 }
@@ -257,7 +670,7 @@ impl Drop for CharLS {
 
 fn translate_error(code: i32) -> CharlsResult<()> {
     if code != 0 {
-        return Err(Error::JpegLsError { code });
+        return Err(Error::from_code(code));
     }
 
     Ok(())